@@ -9,7 +9,16 @@ fn main() {
     let input = Path::new("examples/example.svg");
     let output = tempdir.path().join("icon.ico");
 
-    svg_to_ico::svg_to_ico(input, 96.0, &output, &[32, 64]).expect("failed to convert svg to ico");
+    svg_to_ico::svg_to_ico(
+        input,
+        96.0,
+        &output,
+        svg_to_ico::IconSpec::Ico(&[32, 64]),
+        &svg_to_ico::FontOptions::default(),
+        None,
+        false,
+    )
+    .expect("failed to convert svg to ico");
 
     assert!(output.exists());
 }
@@ -6,12 +6,14 @@
 //! with its [raqote](https://github.com/jrmuizel/raqote) backend.
 //!
 //! This crate provides a single function to create an ICO file from an SVG file.
-use std::fs::{create_dir_all, read, File};
-use std::io;
-use std::path::Path;
+use std::fs::{self, create_dir_all, read, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
+use sha2::{Digest, Sha256};
 use tiny_skia::Pixmap;
-use usvg::Tree;
+use usvg::fontdb;
+use usvg::{Tree, TreeParsing, TreeTextToPath};
 
 /// Error returned when creating an ICO file from an SVG file fails.
 #[derive(Debug)]
@@ -24,6 +26,10 @@ pub enum Error {
     ParseError,
     /// Something went wrong when rasterizing the SVG file.
     RasterizeError,
+    /// Something went wrong when loading a font file or directory.
+    FontError(String),
+    /// Something went wrong when parsing a background colour.
+    ColorError(String),
 }
 
 impl From<std::io::Error> for Error {
@@ -39,16 +45,264 @@ impl std::fmt::Display for Error {
             Error::NulError(ref e) => e.fmt(f),
             Error::ParseError => write!(f, "An unknown SVG parsing error"),
             Error::RasterizeError => write!(f, "Failed to rasterize SVG"),
+            Error::FontError(ref e) => write!(f, "Failed to load font: {}", e),
+            Error::ColorError(ref e) => write!(f, "Failed to parse background colour: {}", e),
         }
     }
 }
 
-/// Create a new ICO file from given SVG file.
+/// Parses a background colour from a `#rrggbb` or `#rrggbbaa` hex string, or a named CSS colour.
 ///
-/// SVG dimensions are interpreted as pixels and the image rasterized using the given DPI. The ICO
-/// entry sizes are the heights in pixels of the images to store inside the ICO file: the SVG image
-/// will be scaled to produce images of the specified sizes. If the ICO
-/// file's parent directory does not exist, it will be created.
+/// The parsed colour is used to fill each [`Pixmap`] before the SVG is rendered on top of it, so
+/// that the output icon has an opaque backdrop instead of a transparent one.
+pub fn parse_background_color(value: &str) -> Result<tiny_skia::Color, Error> {
+    let color = csscolorparser::parse(value).map_err(|e| Error::ColorError(e.to_string()))?;
+    let [r, g, b, a] = color.to_rgba8();
+    Ok(tiny_skia::Color::from_rgba8(r, g, b, a))
+}
+
+/// Options controlling how `<text>` elements are resolved to fonts when rasterising an SVG.
+///
+/// The system's installed fonts are always loaded; the fields below let callers register
+/// additional fonts and override which family is used to resolve the generic `serif`,
+/// `sans-serif` and `monospace` families (and the fallback family used when no match is found).
+#[derive(Debug, Clone, Default)]
+pub struct FontOptions {
+    /// Paths to additional font files to load.
+    pub font_files: Vec<PathBuf>,
+    /// Paths to directories to recursively search for additional font files.
+    pub font_dirs: Vec<PathBuf>,
+    /// Family name to fall back to when a `<text>` element doesn't match any loaded font.
+    pub default_font_family: Option<String>,
+    /// Family name to use when a `<text>` element requests the generic `serif` family.
+    pub serif_family: Option<String>,
+    /// Family name to use when a `<text>` element requests the generic `sans-serif` family.
+    pub sans_serif_family: Option<String>,
+    /// Family name to use when a `<text>` element requests the generic `monospace` family.
+    pub monospace_family: Option<String>,
+}
+
+/// The container format to encode the rasterised icon images into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// A Windows `.ico` icon.
+    #[default]
+    Ico,
+    /// An Apple `.icns` icon.
+    Icns,
+}
+
+impl Format {
+    /// Infers the format from a file's extension, defaulting to [`Format::Ico`] for an
+    /// unrecognised or missing extension.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("icns") => Format::Icns,
+            _ => Format::Ico,
+        }
+    }
+}
+
+/// A single slot in an ICNS icon family.
+///
+/// Apple's ICNS format has separate slots for a "1x" size and its "@2x" retina variant, but the
+/// @2x variant of one size rasterises at the same pixel dimensions as the 1x variant of the next
+/// size up (e.g. both `Size16x16x2` and `Size32x32` are 32x32 images). A plain pixel height is
+/// therefore not enough to pick the right slot; naming the slot directly removes the ambiguity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcnsSize {
+    /// The 16x16 "1x" slot.
+    Size16x16,
+    /// The 16x16 "@2x" slot, a 32x32 image.
+    Size16x16x2,
+    /// The 32x32 "1x" slot.
+    Size32x32,
+    /// The 32x32 "@2x" slot, a 64x64 image.
+    Size32x32x2,
+    /// The 128x128 "1x" slot.
+    Size128x128,
+    /// The 128x128 "@2x" slot, a 256x256 image.
+    Size128x128x2,
+    /// The 256x256 "1x" slot.
+    Size256x256,
+    /// The 256x256 "@2x" slot, a 512x512 image.
+    Size256x256x2,
+    /// The 512x512 "1x" slot.
+    Size512x512,
+    /// The 512x512 "@2x" slot, a 1024x1024 image.
+    Size512x512x2,
+}
+
+impl IcnsSize {
+    /// All ten standard ICNS slots, smallest to largest.
+    pub const ALL: [IcnsSize; 10] = [
+        IcnsSize::Size16x16,
+        IcnsSize::Size16x16x2,
+        IcnsSize::Size32x32,
+        IcnsSize::Size32x32x2,
+        IcnsSize::Size128x128,
+        IcnsSize::Size128x128x2,
+        IcnsSize::Size256x256,
+        IcnsSize::Size256x256x2,
+        IcnsSize::Size512x512,
+        IcnsSize::Size512x512x2,
+    ];
+
+    /// Parses a slot from its CLI representation, e.g. `"32"` or `"32@2x"`.
+    pub fn parse(value: &str) -> Option<IcnsSize> {
+        match value {
+            "16" => Some(IcnsSize::Size16x16),
+            "16@2x" => Some(IcnsSize::Size16x16x2),
+            "32" => Some(IcnsSize::Size32x32),
+            "32@2x" => Some(IcnsSize::Size32x32x2),
+            "128" => Some(IcnsSize::Size128x128),
+            "128@2x" => Some(IcnsSize::Size128x128x2),
+            "256" => Some(IcnsSize::Size256x256),
+            "256@2x" => Some(IcnsSize::Size256x256x2),
+            "512" => Some(IcnsSize::Size512x512),
+            "512@2x" => Some(IcnsSize::Size512x512x2),
+            _ => None,
+        }
+    }
+
+    /// The height in pixels to rasterise the SVG at to fill this slot.
+    pub fn pixel_height(self) -> u16 {
+        match self {
+            IcnsSize::Size16x16 => 16,
+            IcnsSize::Size16x16x2 => 32,
+            IcnsSize::Size32x32 => 32,
+            IcnsSize::Size32x32x2 => 64,
+            IcnsSize::Size128x128 => 128,
+            IcnsSize::Size128x128x2 => 256,
+            IcnsSize::Size256x256 => 256,
+            IcnsSize::Size256x256x2 => 512,
+            IcnsSize::Size512x512 => 512,
+            IcnsSize::Size512x512x2 => 1024,
+        }
+    }
+
+    fn icon_type(self) -> icns::IconType {
+        match self {
+            IcnsSize::Size16x16 => icns::IconType::RGBA32_16x16,
+            IcnsSize::Size16x16x2 => icns::IconType::RGBA32_16x16_2x,
+            IcnsSize::Size32x32 => icns::IconType::RGBA32_32x32,
+            IcnsSize::Size32x32x2 => icns::IconType::RGBA32_32x32_2x,
+            IcnsSize::Size128x128 => icns::IconType::RGBA32_128x128,
+            IcnsSize::Size128x128x2 => icns::IconType::RGBA32_128x128_2x,
+            IcnsSize::Size256x256 => icns::IconType::RGBA32_256x256,
+            IcnsSize::Size256x256x2 => icns::IconType::RGBA32_256x256_2x,
+            IcnsSize::Size512x512 => icns::IconType::RGBA32_512x512,
+            IcnsSize::Size512x512x2 => icns::IconType::RGBA32_512x512_2x,
+        }
+    }
+}
+
+/// The container format to produce, together with the sizes to include in it.
+#[derive(Debug, Clone, Copy)]
+pub enum IconSpec<'a> {
+    /// Produce a Windows `.ico` file containing images with the given heights in pixels.
+    Ico(&'a [u16]),
+    /// Produce an Apple `.icns` file containing the given slots.
+    Icns(&'a [IcnsSize]),
+}
+
+impl<'a> IconSpec<'a> {
+    /// The heights in pixels to rasterise the SVG at to produce each image this spec needs.
+    pub fn pixel_heights(&self) -> Vec<u16> {
+        match self {
+            IconSpec::Ico(sizes) => sizes.to_vec(),
+            IconSpec::Icns(sizes) => sizes.iter().map(|size| size.pixel_height()).collect(),
+        }
+    }
+
+    /// A filename-safe label for each image this spec needs, in the same order as
+    /// [`IconSpec::pixel_heights`], e.g. `"32"` for an ICO size or `"32@2x"` for an ICNS slot.
+    pub fn labels(&self) -> Vec<String> {
+        match self {
+            IconSpec::Ico(sizes) => sizes.iter().map(|size| size.to_string()).collect(),
+            IconSpec::Icns(sizes) => sizes.iter().copied().map(icns_size_label).collect(),
+        }
+    }
+
+    fn encode(&self, images: Vec<Pixmap>) -> Result<Vec<u8>, Error> {
+        match self {
+            IconSpec::Ico(_) => encode_ico(images).map_err(Error::from),
+            IconSpec::Icns(sizes) => encode_icns(sizes, images).map_err(Error::from),
+        }
+    }
+}
+
+fn icns_size_label(size: IcnsSize) -> String {
+    match size {
+        IcnsSize::Size16x16 => "16",
+        IcnsSize::Size16x16x2 => "16@2x",
+        IcnsSize::Size32x32 => "32",
+        IcnsSize::Size32x32x2 => "32@2x",
+        IcnsSize::Size128x128 => "128",
+        IcnsSize::Size128x128x2 => "128@2x",
+        IcnsSize::Size256x256 => "256",
+        IcnsSize::Size256x256x2 => "256@2x",
+        IcnsSize::Size512x512 => "512",
+        IcnsSize::Size512x512x2 => "512@2x",
+    }
+    .to_string()
+}
+
+fn build_usvg_options(
+    svg_dpi: f32,
+    font_options: &FontOptions,
+) -> Result<(usvg::Options, fontdb::Database), Error> {
+    let mut fontdb = fontdb::Database::new();
+    fontdb.load_system_fonts();
+
+    for font_file in &font_options.font_files {
+        fontdb
+            .load_font_file(font_file)
+            .map_err(|e| Error::FontError(e.to_string()))?;
+    }
+
+    for font_dir in &font_options.font_dirs {
+        fontdb.load_fonts_dir(font_dir);
+    }
+
+    if let Some(family) = &font_options.serif_family {
+        fontdb.set_serif_family(family.clone());
+    }
+    if let Some(family) = &font_options.sans_serif_family {
+        fontdb.set_sans_serif_family(family.clone());
+    }
+    if let Some(family) = &font_options.monospace_family {
+        fontdb.set_monospace_family(family.clone());
+    }
+
+    let mut opt = usvg::Options {
+        dpi: svg_dpi,
+        ..Default::default()
+    };
+
+    if let Some(family) = &font_options.default_font_family {
+        opt.font_family = family.clone();
+    }
+
+    Ok((opt, fontdb))
+}
+
+/// Create a new ICO or ICNS file from given SVG file.
+///
+/// SVG dimensions are interpreted as pixels and the image rasterized using the given DPI.
+/// `icon_spec` selects the output container and the sizes to include in it: [`IconSpec::Ico`]
+/// produces a Windows `.ico` file containing images at the given pixel heights, while
+/// [`IconSpec::Icns`] produces an Apple `.icns` file containing the given named slots. If the
+/// output file's parent directory does not exist, it will be created.
+///
+/// `background`, if given, is used to fill each image before the SVG is rendered on top of it,
+/// giving the icon an opaque backdrop instead of a transparent one. Use
+/// [`parse_background_color`] to build one from a hex or named CSS colour string.
+///
+/// A digest of the SVG content and the `svg_dpi`, `icon_spec`, `font_options` and `background`
+/// parameters is recorded in a `.cachekey` file next to `ico_path`. If `ico_path` already exists
+/// and its recorded digest matches, conversion is skipped; pass `force: true` to always
+/// reconvert.
 ///
 /// ## Examples
 ///
@@ -58,14 +312,14 @@ impl std::fmt::Display for Error {
 /// ```
 /// # extern crate svg_to_ico;
 /// use std::path::Path;
-/// use svg_to_ico::svg_to_ico;
+/// use svg_to_ico::{svg_to_ico, FontOptions, IconSpec};
 ///
 /// # fn main() { run().unwrap() }
 /// # fn run() -> Result<(), svg_to_ico::Error> {
 /// let input = Path::new("examples/example.svg");
 /// let output = Path::new("examples/example.ico");
 ///
-/// svg_to_ico(input, 96.0, output, &[32, 64])?;
+/// svg_to_ico(input, 96.0, output, IconSpec::Ico(&[32, 64]), &FontOptions::default(), None, false)?;
 /// #     Ok(())
 /// # }
 /// ```
@@ -73,47 +327,239 @@ pub fn svg_to_ico(
     svg_path: &Path,
     svg_dpi: f32,
     ico_path: &Path,
-    ico_entry_sizes: &[u16],
+    icon_spec: IconSpec,
+    font_options: &FontOptions,
+    background: Option<tiny_skia::Color>,
+    force: bool,
 ) -> Result<(), Error> {
-    let opt = usvg::Options {
-        dpi: svg_dpi,
-        ..Default::default()
-    };
+    let file_content = read(svg_path)?;
+
+    let cache_key = compute_cache_key(&file_content, svg_dpi, &icon_spec, font_options, background);
+    let cache_key_path = cache_key_path(ico_path);
+
+    if !force && ico_path.exists() {
+        if let Ok(existing_cache_key) = fs::read_to_string(&cache_key_path) {
+            if existing_cache_key == cache_key {
+                return Ok(());
+            }
+        }
+    }
+
+    let encoded = svg_bytes_to_ico(&file_content, svg_dpi, icon_spec, font_options, background)?;
 
+    if let Some(p) = ico_path.parent() {
+        create_dir_all(p)?;
+    }
+
+    let mut file = File::create(ico_path)?;
+    file.write_all(&encoded)?;
+
+    fs::write(&cache_key_path, &cache_key)?;
+
+    Ok(())
+}
+
+/// Like [`svg_to_ico`], but also returns a standalone encoded PNG per size in `icon_spec`.
+///
+/// This is used when both an ICO/ICNS file and a directory of PNG sidecars are wanted from the
+/// same SVG: the file is parsed and rasterised exactly once and the resulting images are reused
+/// for both outputs, instead of calling [`svg_to_ico`] and [`svg_to_pngs`] separately and
+/// rasterising everything twice. The ICO/ICNS file is still only rewritten when the cache key
+/// doesn't match (see [`svg_to_ico`]), but the returned PNGs always reflect a fresh rasterisation.
+pub fn svg_to_ico_and_pngs(
+    svg_path: &Path,
+    svg_dpi: f32,
+    ico_path: &Path,
+    icon_spec: IconSpec,
+    font_options: &FontOptions,
+    background: Option<tiny_skia::Color>,
+    force: bool,
+) -> Result<Vec<Vec<u8>>, Error> {
     let file_content = read(svg_path)?;
-    let svg = Tree::from_data(&file_content, &opt).map_err(|_| Error::ParseError)?;
 
-    let images = ico_entry_sizes
+    let cache_key = compute_cache_key(&file_content, svg_dpi, &icon_spec, font_options, background);
+    let cache_key_path = cache_key_path(ico_path);
+
+    let (opt, fontdb) = build_usvg_options(svg_dpi, font_options)?;
+    let mut svg = Tree::from_data(&file_content, &opt).map_err(|_| Error::ParseError)?;
+    svg.convert_text(&fontdb);
+    let images = rasterize_all(&svg, &icon_spec.pixel_heights(), background)?;
+
+    let up_to_date = !force
+        && ico_path.exists()
+        && fs::read_to_string(&cache_key_path)
+            .map(|existing| existing == cache_key)
+            .unwrap_or(false);
+
+    if !up_to_date {
+        let encoded = icon_spec.encode(images.clone())?;
+
+        if let Some(p) = ico_path.parent() {
+            create_dir_all(p)?;
+        }
+
+        let mut file = File::create(ico_path)?;
+        file.write_all(&encoded)?;
+
+        fs::write(&cache_key_path, &cache_key)?;
+    }
+
+    images
+        .into_iter()
+        .map(|pixmap| pixmap.encode_png().map_err(|_| Error::RasterizeError))
+        .collect()
+}
+
+/// Builds the path of the sidecar file used to cache the parameters an icon was last built with.
+fn cache_key_path(ico_path: &Path) -> PathBuf {
+    let mut file_name = ico_path.as_os_str().to_os_string();
+    file_name.push(".cachekey");
+    PathBuf::from(file_name)
+}
+
+/// Computes a hex-encoded digest of the SVG content and the conversion parameters that affect
+/// the output, for use as a build cache key.
+fn compute_cache_key(
+    svg_data: &[u8],
+    svg_dpi: f32,
+    icon_spec: &IconSpec,
+    font_options: &FontOptions,
+    background: Option<tiny_skia::Color>,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(svg_data);
+    hasher.update(svg_dpi.to_le_bytes());
+    hasher.update(format!("{:?}", icon_spec).as_bytes());
+    hasher.update(format!("{:?}", font_options).as_bytes());
+    hasher.update(format!("{:?}", background).as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Create an encoded ICO or ICNS buffer from SVG data held in memory.
+///
+/// This behaves like [`svg_to_ico`], but takes the SVG content directly rather than reading it
+/// from a file, and returns the encoded bytes rather than writing them to a file. This allows
+/// conversion to happen entirely in memory, e.g. within a build script or web service.
+///
+/// ## Examples
+///
+/// ```
+/// # extern crate svg_to_ico;
+/// use svg_to_ico::{svg_bytes_to_ico, FontOptions, IconSpec};
+///
+/// # fn main() { run().unwrap() }
+/// # fn run() -> Result<(), svg_to_ico::Error> {
+/// let svg_data = std::fs::read("examples/example.svg").unwrap();
+///
+/// let ico_data = svg_bytes_to_ico(&svg_data, 96.0, IconSpec::Ico(&[32, 64]), &FontOptions::default(), None)?;
+/// #     Ok(())
+/// # }
+/// ```
+pub fn svg_bytes_to_ico(
+    svg_data: &[u8],
+    svg_dpi: f32,
+    icon_spec: IconSpec,
+    font_options: &FontOptions,
+    background: Option<tiny_skia::Color>,
+) -> Result<Vec<u8>, Error> {
+    let (opt, fontdb) = build_usvg_options(svg_dpi, font_options)?;
+
+    let mut svg = Tree::from_data(svg_data, &opt).map_err(|_| Error::ParseError)?;
+    svg.convert_text(&fontdb);
+
+    let images = rasterize_all(&svg, &icon_spec.pixel_heights(), background)?;
+
+    icon_spec.encode(images)
+}
+
+/// Rasterise the given SVG file at each of `heights` and return the resulting images.
+///
+/// This is the parsing and rasterisation step shared by [`svg_to_ico`] and [`svg_to_pngs`]. Call
+/// it directly, as [`svg_to_ico_and_pngs`] does, to rasterise an SVG file exactly once and reuse
+/// the result for more than one kind of output.
+pub fn rasterize_svg_file(
+    svg_path: &Path,
+    svg_dpi: f32,
+    heights: &[u16],
+    font_options: &FontOptions,
+    background: Option<tiny_skia::Color>,
+) -> Result<Vec<Pixmap>, Error> {
+    let (opt, fontdb) = build_usvg_options(svg_dpi, font_options)?;
+
+    let file_content = read(svg_path)?;
+    let mut svg = Tree::from_data(&file_content, &opt).map_err(|_| Error::ParseError)?;
+    svg.convert_text(&fontdb);
+
+    rasterize_all(&svg, heights, background)
+}
+
+fn rasterize_all(
+    svg: &Tree,
+    heights: &[u16],
+    background: Option<tiny_skia::Color>,
+) -> Result<Vec<Pixmap>, Error> {
+    heights
         .iter()
-        .map(|size| rasterize(&svg, *size))
-        .collect::<Result<Vec<_>, Error>>()?;
+        .map(|height| rasterize(svg, *height, background))
+        .collect()
+}
 
-    create_ico(ico_path, images).map_err(Error::from)
+/// Rasterise the given SVG file at each of `sizes` and encode each result as a standalone PNG.
+///
+/// Returns one encoded PNG buffer per entry in `sizes`, in the same order. This calls
+/// [`rasterize_svg_file`] internally; to share the parse and rasterisation with another output
+/// (e.g. an ICO/ICNS file), call [`svg_to_ico_and_pngs`] or [`rasterize_svg_file`] directly
+/// instead of this function.
+pub fn svg_to_pngs(
+    svg_path: &Path,
+    svg_dpi: f32,
+    sizes: &[u16],
+    font_options: &FontOptions,
+    background: Option<tiny_skia::Color>,
+) -> Result<Vec<Vec<u8>>, Error> {
+    rasterize_svg_file(svg_path, svg_dpi, sizes, font_options, background)?
+        .into_iter()
+        .map(|pixmap| pixmap.encode_png().map_err(|_| Error::RasterizeError))
+        .collect()
 }
 
-fn rasterize(svg: &Tree, height_in_pixels: u16) -> Result<Pixmap, Error> {
+fn rasterize(
+    svg: &Tree,
+    height_in_pixels: u16,
+    background: Option<tiny_skia::Color>,
+) -> Result<Pixmap, Error> {
     let target_height: f32 = height_in_pixels.into();
     let target_size = tiny_skia::Size::from_wh(target_height, target_height)
         .expect("Unsigned values should always be valid");
 
-    let scaled_size = svg.size().scale_to(target_size);
+    let scaled_size = svg.size.scale_to(target_size);
 
-    let sx = scaled_size.width() / svg.size().width();
-    let sy = scaled_size.height() / svg.size().height();
+    let sx = scaled_size.width() / svg.size.width();
+    let sy = scaled_size.height() / svg.size.height();
     let transform = tiny_skia::Transform::from_scale(sx, sy);
 
     let pixmap_size = scaled_size.to_int_size();
 
     Pixmap::new(pixmap_size.width(), pixmap_size.height())
         .map(|mut pixmap| {
+            if let Some(color) = background {
+                pixmap.fill(color);
+            }
+
+            let rtree = resvg::Tree::from_usvg(svg);
             let mut pixmap_mut = pixmap.as_mut();
-            resvg::render(svg, transform, &mut pixmap_mut);
+            rtree.render(transform, &mut pixmap_mut);
             pixmap
         })
         .ok_or(Error::RasterizeError)
 }
 
-fn create_ico(ico_path: &Path, pngs: Vec<Pixmap>) -> io::Result<()> {
+fn encode_ico(pngs: Vec<Pixmap>) -> io::Result<Vec<u8>> {
     let mut icon_dir = ico::IconDir::new(ico::ResourceType::Icon);
 
     for png in pngs {
@@ -121,12 +567,26 @@ fn create_ico(ico_path: &Path, pngs: Vec<Pixmap>) -> io::Result<()> {
         icon_dir.add_entry(ico::IconDirEntry::encode(&image)?);
     }
 
-    if let Some(p) = ico_path.parent() {
-        create_dir_all(p)?;
+    let mut encoded = Vec::new();
+    icon_dir.write(&mut encoded)?;
+    Ok(encoded)
+}
+
+fn encode_icns(sizes: &[IcnsSize], pngs: Vec<Pixmap>) -> io::Result<Vec<u8>> {
+    let mut family = icns::IconFamily::new();
+
+    for (size, png) in sizes.iter().zip(pngs) {
+        let image = icns::Image::from_data(icns::PixelFormat::RGBA, png.width(), png.height(), png.take())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        family
+            .add_icon_with_type(&image, size.icon_type())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
     }
 
-    let file = File::create(ico_path)?;
-    icon_dir.write(file)
+    let mut encoded = Vec::new();
+    family.write(&mut encoded)?;
+    Ok(encoded)
 }
 
 #[cfg(test)]
@@ -136,7 +596,7 @@ mod tests {
     fn load_svg(path: &Path) -> Tree {
         let svg_dpi = 96.0;
 
-        let opt = usvg::Options::<'_> {
+        let opt = usvg::Options {
             dpi: svg_dpi,
             ..Default::default()
         };
@@ -145,15 +605,78 @@ mod tests {
         Tree::from_data(&file_content, &opt).unwrap()
     }
 
+    #[test]
+    fn build_usvg_options_should_return_a_font_error_for_an_unreadable_font_file() {
+        let font_options = FontOptions {
+            font_files: vec![PathBuf::from("examples/does-not-exist.ttf")],
+            ..FontOptions::default()
+        };
+
+        let result = build_usvg_options(96.0, &font_options);
+
+        assert!(matches!(result, Err(Error::FontError(_))));
+    }
+
+    #[test]
+    fn rasterize_should_render_text_using_the_configured_default_font_family() {
+        let svg_path = Path::new("examples/text.svg");
+        let font_options = FontOptions {
+            default_font_family: Some("DejaVu Sans".to_string()),
+            ..FontOptions::default()
+        };
+
+        let (opt, fontdb) = build_usvg_options(96.0, &font_options).unwrap();
+        let file_content = read(svg_path).unwrap();
+        let mut svg = Tree::from_data(&file_content, &opt).unwrap();
+        svg.convert_text(&fontdb);
+
+        let background = tiny_skia::Color::from_rgba8(255, 255, 255, 255);
+        let image = rasterize(&svg, 24, Some(background)).unwrap();
+
+        // If the configured default font family didn't reach text resolution, the glyph
+        // wouldn't be found and the image would be left as a plain background-coloured square.
+        let pixels = image.take();
+        let is_background_pixel = |pixel: &[u8]| pixel == [255, 255, 255, 255];
+        assert!(
+            pixels.chunks(4).any(|pixel| !is_background_pixel(pixel)),
+            "expected the rendered text to paint at least one non-background pixel"
+        );
+    }
+
+    #[test]
+    fn build_usvg_options_should_apply_generic_family_overrides_to_the_font_database() {
+        let font_options = FontOptions {
+            serif_family: Some("Overridden Serif".to_string()),
+            sans_serif_family: Some("Overridden Sans".to_string()),
+            monospace_family: Some("Overridden Mono".to_string()),
+            ..FontOptions::default()
+        };
+
+        let (_, fontdb) = build_usvg_options(96.0, &font_options).unwrap();
+
+        assert_eq!(
+            "Overridden Serif",
+            fontdb.family_name(&fontdb::Family::Serif)
+        );
+        assert_eq!(
+            "Overridden Sans",
+            fontdb.family_name(&fontdb::Family::SansSerif)
+        );
+        assert_eq!(
+            "Overridden Mono",
+            fontdb.family_name(&fontdb::Family::Monospace)
+        );
+    }
+
     #[test]
     fn rasterize_should_scale_svg_to_given_height() {
         let svg_path = Path::new("examples/example.svg");
         let svg = load_svg(svg_path);
 
-        assert_eq!(24.0, svg.size().height());
-        assert_eq!(24.0, svg.size().width());
+        assert_eq!(24.0, svg.size.height());
+        assert_eq!(24.0, svg.size.width());
 
-        let image = rasterize(&svg, 400).unwrap();
+        let image = rasterize(&svg, 400, None).unwrap();
         assert_eq!(400, image.height());
         assert_eq!(400, image.width());
     }
@@ -163,23 +686,109 @@ mod tests {
         let svg_path = Path::new("examples/example.svg");
         let svg = load_svg(svg_path);
 
-        let image = rasterize(&svg, 24).unwrap();
+        let image = rasterize(&svg, 24, None).unwrap();
         let pixel_index = 24 * 6 + 12;
         let pixel = &image.take()[pixel_index * 4..(pixel_index + 1) * 4];
 
         assert_eq!(&[50, 100, 150, 255], pixel);
     }
 
+    #[test]
+    fn rasterize_should_fill_background_colour_before_rendering_svg() {
+        let svg_path = Path::new("examples/example.svg");
+        let svg = load_svg(svg_path);
+
+        let background = tiny_skia::Color::from_rgba8(255, 0, 0, 255);
+        let image = rasterize(&svg, 24, Some(background)).unwrap();
+
+        // A corner pixel outside the rendered SVG content should show the background colour.
+        let pixel = &image.take()[0..4];
+        assert_eq!(&[255, 0, 0, 255], pixel);
+    }
+
     #[test]
     fn rasterize_should_scale_svg_with_width_longer_than_height() {
         let svg_path = Path::new("examples/landscape.svg");
         let svg = load_svg(svg_path);
 
-        assert_eq!(24.0, svg.size().height());
-        assert_eq!(48.0, svg.size().width());
+        assert_eq!(24.0, svg.size.height());
+        assert_eq!(48.0, svg.size.width());
 
-        let image = rasterize(&svg, 400).unwrap();
+        let image = rasterize(&svg, 400, None).unwrap();
         assert_eq!(200, image.height());
         assert_eq!(400, image.width());
     }
+
+    #[test]
+    fn svg_bytes_to_ico_should_produce_a_valid_ico_file() {
+        let svg_data = read("examples/example.svg").unwrap();
+
+        let ico_data = svg_bytes_to_ico(
+            &svg_data,
+            96.0,
+            IconSpec::Ico(&[16, 32]),
+            &FontOptions::default(),
+            None,
+        )
+        .unwrap();
+
+        // ICO files start with a 6-byte header: reserved (0), type (1 for icon) and image count.
+        assert_eq!(&[0, 0, 1, 0, 2, 0], &ico_data[0..6]);
+    }
+
+    #[test]
+    fn compute_cache_key_should_change_when_font_options_change() {
+        let svg_data = read("examples/example.svg").unwrap();
+        let icon_spec = IconSpec::Ico(&[32]);
+
+        let default_key =
+            compute_cache_key(&svg_data, 96.0, &icon_spec, &FontOptions::default(), None);
+
+        let custom_font_options = FontOptions {
+            default_font_family: Some("Comic Sans MS".to_string()),
+            ..FontOptions::default()
+        };
+        let custom_key =
+            compute_cache_key(&svg_data, 96.0, &icon_spec, &custom_font_options, None);
+
+        assert_ne!(default_key, custom_key);
+    }
+
+    #[test]
+    fn svg_to_ico_should_skip_conversion_on_cache_hit_and_reconvert_on_cache_miss_or_force() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let svg_path = Path::new("examples/example.svg");
+        let ico_path = tempdir.path().join("icon.ico");
+        let icon_spec = IconSpec::Ico(&[32]);
+        let font_options = FontOptions::default();
+
+        svg_to_ico(svg_path, 96.0, &ico_path, icon_spec, &font_options, None, false).unwrap();
+        let first_written = read(&ico_path).unwrap();
+
+        // Tamper with the output so a skipped conversion is distinguishable from a real one.
+        fs::write(&ico_path, b"tampered").unwrap();
+
+        // Cache hit: same parameters should leave the tampered file alone.
+        svg_to_ico(svg_path, 96.0, &ico_path, icon_spec, &font_options, None, false).unwrap();
+        assert_eq!(b"tampered".to_vec(), read(&ico_path).unwrap());
+
+        // Cache miss: different parameters should reconvert.
+        let other_icon_spec = IconSpec::Ico(&[64]);
+        svg_to_ico(
+            svg_path,
+            96.0,
+            &ico_path,
+            other_icon_spec,
+            &font_options,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_ne!(b"tampered".to_vec(), read(&ico_path).unwrap());
+
+        // force: true should reconvert even on a cache hit.
+        fs::write(&ico_path, b"tampered").unwrap();
+        svg_to_ico(svg_path, 96.0, &ico_path, icon_spec, &font_options, None, true).unwrap();
+        assert_eq!(first_written, read(&ico_path).unwrap());
+    }
 }
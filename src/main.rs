@@ -2,6 +2,8 @@ use clap::value_parser;
 use clap::{Arg, ArgAction, Command};
 use std::path::PathBuf;
 
+use svg_to_ico::{Format, FontOptions, IcnsSize, IconSpec};
+
 fn main() {
     let matches = Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
@@ -32,7 +34,7 @@ fn main() {
                 .value_name("FILE")
                 .value_parser(value_parser!(PathBuf))
                 .help("Output path for the ICO file")
-                .required(true),
+                .required_unless_present("png_dir"),
         )
         .arg(
             Arg::new("ico_sizes")
@@ -42,12 +44,86 @@ fn main() {
                 .value_parser(value_parser!(u16))
                 .action(ArgAction::Append)
                 .num_args(1..)
-                .default_values(&[
+                .default_values([
                     "16", "20", "24", "30", "32", "36", "40", "48", "60", "64", "72", "80", "96",
                     "128", "256",
                 ])
                 .long_help("An image size (height in pixels) to include within the ICO file."),
         )
+        .arg(
+            Arg::new("icns_sizes")
+                .long("icns-size")
+                .value_name("SIZE")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .help("An ICNS icon slot to include when --format is \"icns\", e.g. \"32\" or \"32@2x\"; defaults to all standard slots"),
+        )
+        .arg(
+            Arg::new("font_files")
+                .long("font-file")
+                .value_name("FILE")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .help("Path to an additional font file to load"),
+        )
+        .arg(
+            Arg::new("font_dirs")
+                .long("font-dir")
+                .value_name("DIR")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .help("Path to a directory to recursively search for additional font files"),
+        )
+        .arg(
+            Arg::new("default_font_family")
+                .long("default-font-family")
+                .value_name("FAMILY")
+                .help("Font family to fall back to when a <text> element doesn't match any loaded font"),
+        )
+        .arg(
+            Arg::new("serif_family")
+                .long("serif-family")
+                .value_name("FAMILY")
+                .help("Font family to use for the generic 'serif' font family"),
+        )
+        .arg(
+            Arg::new("sans_serif_family")
+                .long("sans-serif-family")
+                .value_name("FAMILY")
+                .help("Font family to use for the generic 'sans-serif' font family"),
+        )
+        .arg(
+            Arg::new("monospace_family")
+                .long("monospace-family")
+                .value_name("FAMILY")
+                .help("Font family to use for the generic 'monospace' font family"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(["ico", "icns"])
+                .help("Output format, inferred from the output file's extension if not given"),
+        )
+        .arg(
+            Arg::new("background")
+                .long("background")
+                .value_name("COLOR")
+                .help("Background colour to fill each image with before rendering the SVG on top of it, e.g. #rrggbb, #rrggbbaa or a named CSS colour"),
+        )
+        .arg(
+            Arg::new("png_dir")
+                .long("png-dir")
+                .value_name("DIR")
+                .value_parser(value_parser!(PathBuf))
+                .help("Directory to write a standalone icon-<size>.png file for each image in --size (or --icns-size, if --format is \"icns\") into, in addition to (or instead of, if --output is omitted) the ICO/ICNS file"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .help("Reconvert even if the output file's cached conversion parameters already match"),
+        )
         .get_matches();
 
     let svg_path = matches
@@ -57,14 +133,113 @@ fn main() {
         .get_one::<f32>("svg_dpi")
         .copied()
         .expect("svg_dpi is has a default value");
-    let ico_path = matches
-        .get_one::<PathBuf>("ico_path")
-        .expect("ico_path is required");
+    let ico_path = matches.get_one::<PathBuf>("ico_path");
     let ico_sizes: Vec<u16> = matches
         .get_many("ico_sizes")
         .expect("ico_sizes has a default value")
         .copied()
         .collect();
 
-    svg_to_ico::svg_to_ico(svg_path, svg_dpi, ico_path, &ico_sizes).unwrap();
+    let font_options = FontOptions {
+        font_files: matches
+            .get_many::<PathBuf>("font_files")
+            .map(|v| v.cloned().collect())
+            .unwrap_or_default(),
+        font_dirs: matches
+            .get_many::<PathBuf>("font_dirs")
+            .map(|v| v.cloned().collect())
+            .unwrap_or_default(),
+        default_font_family: matches.get_one::<String>("default_font_family").cloned(),
+        serif_family: matches.get_one::<String>("serif_family").cloned(),
+        sans_serif_family: matches.get_one::<String>("sans_serif_family").cloned(),
+        monospace_family: matches.get_one::<String>("monospace_family").cloned(),
+    };
+
+    let format = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("ico") => Format::Ico,
+        Some("icns") => Format::Icns,
+        Some(_) => unreachable!("format is restricted to \"ico\" or \"icns\""),
+        None => ico_path.map(|p| Format::from_path(p)).unwrap_or_default(),
+    };
+
+    let icns_sizes: Vec<IcnsSize> = matches
+        .get_many::<String>("icns_sizes")
+        .map(|values| {
+            values
+                .filter_map(|value| match IcnsSize::parse(value) {
+                    Some(size) => Some(size),
+                    None => {
+                        eprintln!("Warning: {} is not a valid ICNS icon slot, skipping", value);
+                        None
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_else(|| IcnsSize::ALL.to_vec());
+
+    let icon_spec = match format {
+        Format::Ico => IconSpec::Ico(&ico_sizes),
+        Format::Icns => IconSpec::Icns(&icns_sizes),
+    };
+
+    let background = matches
+        .get_one::<String>("background")
+        .map(|value| svg_to_ico::parse_background_color(value).unwrap());
+
+    let force = matches.get_flag("force");
+
+    let png_dir = matches.get_one::<PathBuf>("png_dir");
+
+    match (ico_path, png_dir) {
+        (Some(ico_path), Some(png_dir)) => {
+            // Rasterise the SVG exactly once and reuse it for both outputs, rather than letting
+            // svg_to_ico and svg_to_pngs each parse and rasterise it independently.
+            let pngs = svg_to_ico::svg_to_ico_and_pngs(
+                svg_path,
+                svg_dpi,
+                ico_path,
+                icon_spec,
+                &font_options,
+                background,
+                force,
+            )
+            .unwrap();
+
+            write_pngs(png_dir, &icon_spec.labels(), pngs);
+        }
+        (Some(ico_path), None) => {
+            svg_to_ico::svg_to_ico(
+                svg_path,
+                svg_dpi,
+                ico_path,
+                icon_spec,
+                &font_options,
+                background,
+                force,
+            )
+            .unwrap();
+        }
+        (None, Some(png_dir)) => {
+            let pngs = svg_to_ico::svg_to_pngs(
+                svg_path,
+                svg_dpi,
+                &icon_spec.pixel_heights(),
+                &font_options,
+                background,
+            )
+            .unwrap();
+
+            write_pngs(png_dir, &icon_spec.labels(), pngs);
+        }
+        (None, None) => unreachable!("clap requires --output or --png-dir"),
+    }
+}
+
+fn write_pngs(png_dir: &std::path::Path, labels: &[String], pngs: Vec<Vec<u8>>) {
+    std::fs::create_dir_all(png_dir).unwrap();
+
+    for (label, png) in labels.iter().zip(pngs) {
+        let png_path = png_dir.join(format!("icon-{}.png", label));
+        std::fs::write(png_path, png).unwrap();
+    }
 }